@@ -12,63 +12,112 @@
 //! according to a provided iterator.
 //!
 //! This is separate from `PartialWrite` because on `WouldBlock` errors, it
-//! causes `futures` to try writing or flushing again.
+//! causes the task to be re-polled instead of returning an error straight
+//! through to the caller.
+//!
+//! The wrapper is implemented against four async ecosystems, each gated by its own Cargo
+//! feature (`tokio1`, `tokio03`, `tokio02`, `futures03`), so downstream crates can pick
+//! whichever one matches their own dependency graph instead of vendoring this wrapper
+//! themselves. All of them share the same `PartialOp` iterator logic; only the traits they
+//! implement (and, on the read side, whether it's `tokio`'s `ReadBuf` or a plain `&mut [u8]`,
+//! and whether seeking is one `poll_seek` call, a sync `start_seek`/`poll_complete` pair, or a
+//! polled `start_seek`/`poll_complete` pair) differ.
+//! Besides `AsyncRead` and `AsyncWrite`, `AsyncSeek` and `AsyncBufRead` are also driven by the
+//! op iterator, so buffered and seekable wrapped types can be fuzzed the same way.
 
 use std::cmp;
 use std::fmt;
-use std::io::{self, Read, Write};
-
-use futures::{task, Poll};
-use tokio_io::{AsyncRead, AsyncWrite};
+use std::io;
+use std::marker::PhantomPinned;
+use std::mem;
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll};
 
 use crate::{make_ops, PartialOp};
 
 /// A wrapper that breaks inner `AsyncWrite` instances up according to the
 /// provided iterator.
 ///
-/// Available with the `tokio` feature.
+/// Available with the `tokio1`, `tokio03`, `tokio02`, or `futures03` feature.
 ///
 /// # Examples
 ///
 /// ```rust
-/// extern crate partial_io;
-/// extern crate tokio_core;
-/// extern crate tokio_io;
+/// use std::io;
 ///
-/// use std::io::{self, Cursor};
-///
-/// fn main() {
-///     // Note that this test doesn't demonstrate a limited write because
-///     // tokio-io doesn't have a combinator for that, just write_all.
-///     use tokio_core::reactor::Core;
-///     use tokio_io::io::write_all;
+/// use tokio::io::AsyncWriteExt;
 ///
-///     use partial_io::{PartialAsyncWrite, PartialOp};
+/// use partial_io::{PartialAsyncWrite, PartialOp};
 ///
-///     let writer = Cursor::new(Vec::new());
+/// #[tokio::main]
+/// async fn main() {
+///     // Note that this test doesn't demonstrate a limited write because
+///     // write_all keeps writing until the whole buffer has been written.
+///     let writer = Vec::new();
 ///     let iter = vec![PartialOp::Err(io::ErrorKind::WouldBlock), PartialOp::Limited(2)];
-///     let partial_writer = PartialAsyncWrite::new(writer, iter);
+///     let mut partial_writer = PartialAsyncWrite::new(writer, iter);
 ///     let in_data = vec![1, 2, 3, 4];
 ///
-///     let mut core = Core::new().unwrap();
-///
-///     let write_fut = write_all(partial_writer, in_data);
-///
-///     let (partial_writer, _in_data) = core.run(write_fut).unwrap();
-///     let cursor = partial_writer.into_inner();
-///     let out = cursor.into_inner();
+///     partial_writer.write_all(&in_data).await.unwrap();
+///     let out = partial_writer.into_inner();
 ///     assert_eq!(&out, &[1, 2, 3, 4]);
 /// }
 /// ```
 pub struct PartialAsyncWrite<W> {
     inner: W,
     ops: Box<dyn Iterator<Item = PartialOp> + Send>,
+    closed_tracker: Option<ClosedTracker>,
+    // Set while a `Limited` op governs the buffer exposed by `poll_fill_buf`, and cleared again
+    // by `consume`, so repeated `poll_fill_buf` calls before a `consume` see the same slice.
+    fill_buf_limit: Option<usize>,
 }
 
-impl<W> PartialAsyncWrite<W>
-where
-    W: AsyncWrite,
-{
+/// Controls what happens when a tracked `PartialAsyncWrite` is used after it has been shut down.
+///
+/// See [`PartialAsyncWrite::new_tracked`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum ClosedBehavior {
+    /// Return `io::ErrorKind::NotConnected` from the offending call. This is the default.
+    #[default]
+    ReturnError,
+    /// Panic immediately, pointing at the call that misused the writer.
+    Panic,
+}
+
+#[derive(Debug)]
+struct ClosedTracker {
+    behavior: ClosedBehavior,
+    closed: bool,
+    flushed: bool,
+}
+
+impl ClosedTracker {
+    fn new(behavior: ClosedBehavior) -> Self {
+        ClosedTracker {
+            behavior,
+            closed: false,
+            flushed: true,
+        }
+    }
+
+    fn check_not_closed(&self, what: &str) -> io::Result<()> {
+        if !self.closed {
+            return Ok(());
+        }
+        match self.behavior {
+            ClosedBehavior::ReturnError => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("{} called on a PartialAsyncWrite after shutdown", what),
+            )),
+            ClosedBehavior::Panic => {
+                panic!("{} called on a PartialAsyncWrite after shutdown", what)
+            }
+        }
+    }
+}
+
+impl<W> PartialAsyncWrite<W> {
     /// Creates a new `PartialAsyncWrite` wrapper over the writer with the specified `PartialOp`s.
     pub fn new<I>(inner: W, iter: I) -> Self
     where
@@ -78,9 +127,76 @@ where
         PartialAsyncWrite {
             inner,
             ops: make_ops(iter),
+            closed_tracker: None,
+            fill_buf_limit: None,
         }
     }
 
+    /// Creates a new `PartialAsyncWrite` wrapper that tracks whether `shutdown` has completed.
+    ///
+    /// Once `shutdown` has completed successfully, any further `write` or `flush` is flagged as
+    /// a usage error -- by default that means an `io::ErrorKind::NotConnected` error, but
+    /// [`PartialAsyncWrite::set_closed_behavior`] can switch that to a panic -- and a second
+    /// `shutdown` is flagged the same way. This catches the common class of bugs where a wrapper
+    /// keeps writing to a stream it already closed.
+    pub fn new_tracked<I>(inner: W, iter: I) -> Self
+    where
+        I: IntoIterator<Item = PartialOp> + 'static,
+        I::IntoIter: Send,
+    {
+        PartialAsyncWrite {
+            inner,
+            ops: make_ops(iter),
+            closed_tracker: Some(ClosedTracker::new(ClosedBehavior::default())),
+            fill_buf_limit: None,
+        }
+    }
+
+    /// Creates a new `PartialAsyncWrite` wrapper that injects a single `Poll::Pending` (via a
+    /// synthesized `WouldBlock` error) before every op in `iter`.
+    ///
+    /// This is a common stress pattern for making sure every await point driving this writer is
+    /// actually re-polled correctly; without it, exercising the pattern means hand-authoring an
+    /// alternating `[Err(WouldBlock), op, Err(WouldBlock), op, ...]` sequence.
+    pub fn interleave_pending<I>(inner: W, iter: I) -> Self
+    where
+        I: IntoIterator<Item = PartialOp> + 'static,
+        I::IntoIter: Send,
+    {
+        let interleaved = iter
+            .into_iter()
+            .flat_map(|op| vec![PartialOp::Err(io::ErrorKind::WouldBlock), op]);
+        PartialAsyncWrite::new(inner, interleaved)
+    }
+
+    /// Sets how a tracked writer reacts to misuse after `shutdown`. Has no effect unless this
+    /// writer was created with [`PartialAsyncWrite::new_tracked`].
+    pub fn set_closed_behavior(&mut self, behavior: ClosedBehavior) -> &mut Self {
+        if let Some(tracker) = &mut self.closed_tracker {
+            tracker.behavior = behavior;
+        }
+        self
+    }
+
+    /// Returns whether `shutdown` has completed on this writer.
+    ///
+    /// Always returns `false` for writers not created with
+    /// [`PartialAsyncWrite::new_tracked`].
+    pub fn is_closed(&self) -> bool {
+        self.closed_tracker
+            .as_ref()
+            .is_some_and(|tracker| tracker.closed)
+    }
+
+    /// Returns whether the most recent write has been flushed through to the inner writer.
+    ///
+    /// Always returns `true` for writers not created with [`PartialAsyncWrite::new_tracked`].
+    pub fn is_flushed(&self) -> bool {
+        self.closed_tracker
+            .as_ref()
+            .is_none_or(|tracker| tracker.flushed)
+    }
+
     /// Sets the `PartialOp`s for this reader.
     pub fn set_ops<I>(&mut self, iter: I) -> &mut Self
     where
@@ -100,83 +216,583 @@ where
     pub fn into_inner(self) -> W {
         self.inner
     }
-}
 
-impl<W> Write for PartialAsyncWrite<W>
-where
-    W: Write,
-{
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.ops.next() {
-            Some(PartialOp::Limited(n)) => {
-                let len = cmp::min(n, buf.len());
-                self.inner.write(&buf[..len])
-            }
-            Some(PartialOp::Err(err)) => {
-                if err == io::ErrorKind::WouldBlock {
-                    // Make sure this task is rechecked.
-                    task::park().unpark();
-                }
-                Err(io::Error::new(
-                    err,
-                    "error during write, generated by partial-io",
-                ))
-            }
-            Some(PartialOp::Unlimited) | None => self.inner.write(buf),
+    /// Projects the pin on `self` onto its fields.
+    ///
+    /// Safety: `inner` is the only structurally pinned field, we never move it out from behind
+    /// the `Pin`, and `PartialAsyncWrite` has no `Drop` impl.
+    fn project(
+        self: Pin<&mut Self>,
+    ) -> (
+        Pin<&mut W>,
+        &mut (dyn Iterator<Item = PartialOp> + Send),
+        &mut Option<ClosedTracker>,
+    ) {
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (
+                Pin::new_unchecked(&mut this.inner),
+                &mut *this.ops,
+                &mut this.closed_tracker,
+            )
         }
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        match self.ops.next() {
-            Some(PartialOp::Err(err)) => {
-                if err == io::ErrorKind::WouldBlock {
-                    // Make sure this task is rechecked.
-                    task::park().unpark();
-                }
-                Err(io::Error::new(
-                    err,
-                    "error during flush, generated by partial-io",
-                ))
-            }
-            _ => self.inner.flush(),
+    /// Like [`PartialAsyncWrite::project`], but for the fields the `AsyncBufRead` impls need.
+    fn project_bufread(
+        self: Pin<&mut Self>,
+    ) -> (
+        Pin<&mut W>,
+        &mut (dyn Iterator<Item = PartialOp> + Send),
+        &mut Option<usize>,
+    ) {
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (
+                Pin::new_unchecked(&mut this.inner),
+                &mut *this.ops,
+                &mut this.fill_buf_limit,
+            )
         }
     }
 }
 
-impl<W> AsyncWrite for PartialAsyncWrite<W>
-where
-    W: AsyncWrite,
-{
-    #[inline]
-    fn shutdown(&mut self) -> Poll<(), io::Error> {
-        self.inner.shutdown()
+impl<W> PartialAsyncWrite<AssertUnmoved<W>> {
+    /// Creates a new `PartialAsyncWrite` wrapper that also asserts the inner writer is never
+    /// moved in memory between polls, which would violate the `Pin` contract.
+    pub fn assert_unmoved<I>(inner: W, iter: I) -> Self
+    where
+        I: IntoIterator<Item = PartialOp> + 'static,
+        I::IntoIter: Send,
+    {
+        PartialAsyncWrite::new(AssertUnmoved::new(inner), iter)
     }
 }
 
-// Forwarding impls to support duplex structs.
-impl<W> Read for PartialAsyncWrite<W>
+impl<W> fmt::Debug for PartialAsyncWrite<W>
 where
-    W: AsyncWrite + Read,
+    W: fmt::Debug,
 {
-    #[inline]
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialAsyncWrite")
+            .field("inner", &self.inner)
+            .finish()
     }
 }
 
-impl<W> AsyncRead for PartialAsyncWrite<W> where W: AsyncRead + AsyncWrite {}
+/// A thin wrapper that asserts its inner value is never moved in memory once it has been
+/// polled, to guard against `Pin` contract violations.
+///
+/// Create one with [`PartialAsyncWrite::assert_unmoved`]. It delegates all I/O straight through
+/// to the inner writer (and reader, for duplex types) and adds no behavior beyond the assertion.
+pub struct AssertUnmoved<W> {
+    inner: W,
+    self_ptr: Option<*const ()>,
+    _pin: PhantomPinned,
+}
 
-impl<W> fmt::Debug for PartialAsyncWrite<W>
+impl<W> AssertUnmoved<W> {
+    /// Creates a new `AssertUnmoved` wrapper around `inner`.
+    pub fn new(inner: W) -> Self {
+        AssertUnmoved {
+            inner,
+            self_ptr: None,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Consumes this wrapper, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        // `AssertUnmoved` has a `Drop` impl, so `self.inner` can't be moved out directly; take
+        // it out manually and skip running `Drop` on the now-partially-uninitialized `self`.
+        let this = mem::ManuallyDrop::new(self);
+        unsafe { ptr::read(&this.inner) }
+    }
+
+    /// Projects the pin on `self` onto the `inner` field, after asserting that `self` hasn't
+    /// moved since the last time this was called.
+    fn project(self: Pin<&mut Self>) -> Pin<&mut W> {
+        unsafe {
+            let this = self.get_unchecked_mut();
+            let cur_ptr = this as *const Self as *const ();
+            match this.self_ptr {
+                None => this.self_ptr = Some(cur_ptr),
+                Some(ptr) => {
+                    assert_eq!(ptr, cur_ptr, "AssertUnmoved value was moved between polls")
+                }
+            }
+            Pin::new_unchecked(&mut this.inner)
+        }
+    }
+}
+
+impl<W> Drop for AssertUnmoved<W> {
+    fn drop(&mut self) {
+        if let Some(ptr) = self.self_ptr {
+            let cur_ptr = self as *const Self as *const ();
+            assert_eq!(
+                ptr, cur_ptr,
+                "AssertUnmoved value was moved before being dropped"
+            );
+        }
+    }
+}
+
+impl<W> fmt::Debug for AssertUnmoved<W>
 where
     W: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("PartialAsyncWrite")
+        f.debug_struct("AssertUnmoved")
             .field("inner", &self.inner)
             .finish()
     }
 }
 
+// Each of these macros instantiates the shared `PartialOp`-driven logic against one async
+// ecosystem's `AsyncWrite`/`AsyncRead` traits. `$shutdown_method` exists because futures 0.3
+// calls the close-out method `poll_close` where tokio calls it `poll_shutdown`.
+macro_rules! impl_partial_async_write {
+    ($AsyncWrite:path, $shutdown_method:ident) => {
+        impl<W> $AsyncWrite for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite,
+        {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                let (inner, ops, closed_tracker) = self.project();
+                if let Some(tracker) = &closed_tracker {
+                    if let Err(err) = tracker.check_not_closed("write") {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                let res = match ops.next() {
+                    Some(PartialOp::Limited(n)) => {
+                        let len = cmp::min(n, buf.len());
+                        inner.poll_write(cx, &buf[..len])
+                    }
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during write, generated by partial-io",
+                        )))
+                    }
+                    Some(PartialOp::Unlimited) | None => inner.poll_write(cx, buf),
+                };
+                // Only a write that actually reaches the inner writer un-flushes it.
+                if let Poll::Ready(Ok(n)) = &res {
+                    if *n > 0 {
+                        if let Some(tracker) = closed_tracker {
+                            tracker.flushed = false;
+                        }
+                    }
+                }
+                res
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                let (inner, ops, closed_tracker) = self.project();
+                if let Some(tracker) = &closed_tracker {
+                    if let Err(err) = tracker.check_not_closed("flush") {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                match ops.next() {
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during flush, generated by partial-io",
+                        )))
+                    }
+                    _ => {
+                        let res = inner.poll_flush(cx);
+                        if let Poll::Ready(Ok(())) = &res {
+                            if let Some(tracker) = closed_tracker {
+                                tracker.flushed = true;
+                            }
+                        }
+                        res
+                    }
+                }
+            }
+
+            fn $shutdown_method(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                let (inner, _ops, closed_tracker) = self.project();
+                if let Some(tracker) = &closed_tracker {
+                    // A second shutdown is just as much a usage error as writing after the
+                    // first one.
+                    if let Err(err) = tracker.check_not_closed("shutdown") {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                let res = inner.$shutdown_method(cx);
+                if let Poll::Ready(Ok(())) = &res {
+                    if let Some(tracker) = closed_tracker {
+                        tracker.closed = true;
+                    }
+                }
+                res
+            }
+        }
+
+        impl<W> $AsyncWrite for AssertUnmoved<W>
+        where
+            W: $AsyncWrite,
+        {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                self.project().poll_write(cx, buf)
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                self.project().poll_flush(cx)
+            }
+
+            fn $shutdown_method(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                self.project().$shutdown_method(cx)
+            }
+        }
+    };
+}
+
+// Forwarding impls to support duplex structs, for the `ReadBuf`-based readers (tokio 0.3+).
+#[cfg(any(feature = "tokio1", feature = "tokio03"))]
+macro_rules! impl_partial_async_read_readbuf {
+    ($AsyncRead:path, $AsyncWrite:path, $ReadBuf:ty) => {
+        impl<W> $AsyncRead for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncRead,
+        {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut $ReadBuf,
+            ) -> Poll<io::Result<()>> {
+                let (inner, ops, _closed_tracker) = self.project();
+                match ops.next() {
+                    Some(PartialOp::Limited(n)) => {
+                        let len = cmp::min(n, buf.remaining());
+                        let mut sub_buf = buf.take(len);
+                        match inner.poll_read(cx, &mut sub_buf) {
+                            Poll::Ready(Ok(())) => {
+                                let filled = sub_buf.filled().len();
+                                buf.advance(filled);
+                                Poll::Ready(Ok(()))
+                            }
+                            other => other,
+                        }
+                    }
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during read, generated by partial-io",
+                        )))
+                    }
+                    Some(PartialOp::Unlimited) | None => inner.poll_read(cx, buf),
+                }
+            }
+        }
+
+        impl<W> $AsyncRead for AssertUnmoved<W>
+        where
+            W: $AsyncRead,
+        {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut $ReadBuf,
+            ) -> Poll<io::Result<()>> {
+                self.project().poll_read(cx, buf)
+            }
+        }
+    };
+}
+
+// Forwarding impls to support duplex structs, for the `&mut [u8]`-based readers (tokio 0.2 and
+// futures 0.3).
+#[cfg(any(feature = "tokio02", feature = "futures03"))]
+macro_rules! impl_partial_async_read_buf {
+    ($AsyncRead:path, $AsyncWrite:path) => {
+        impl<W> $AsyncRead for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncRead,
+        {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                let (inner, ops, _closed_tracker) = self.project();
+                match ops.next() {
+                    Some(PartialOp::Limited(n)) => {
+                        let len = cmp::min(n, buf.len());
+                        inner.poll_read(cx, &mut buf[..len])
+                    }
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during read, generated by partial-io",
+                        )))
+                    }
+                    Some(PartialOp::Unlimited) | None => inner.poll_read(cx, buf),
+                }
+            }
+        }
+
+        impl<W> $AsyncRead for AssertUnmoved<W>
+        where
+            W: $AsyncRead,
+        {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                self.project().poll_read(cx, buf)
+            }
+        }
+    };
+}
+
+// `AsyncSeek` where seeking is a single `poll_seek(cx, pos)` call (futures 0.3).
+#[cfg(feature = "futures03")]
+macro_rules! impl_partial_async_seek_poll {
+    ($AsyncSeek:path, $AsyncWrite:path) => {
+        impl<W> $AsyncSeek for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncSeek,
+        {
+            fn poll_seek(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                pos: io::SeekFrom,
+            ) -> Poll<io::Result<u64>> {
+                let (inner, ops, _closed_tracker) = self.project();
+                match ops.next() {
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during seek, generated by partial-io",
+                        )))
+                    }
+                    _ => inner.poll_seek(cx, pos),
+                }
+            }
+        }
+    };
+}
+
+// `AsyncSeek` where seeking is split into a synchronous `start_seek` and a polled
+// `poll_complete` (tokio 0.3 and 1.x).
+#[cfg(any(feature = "tokio1", feature = "tokio03"))]
+macro_rules! impl_partial_async_seek_split {
+    ($AsyncSeek:path, $AsyncWrite:path) => {
+        impl<W> $AsyncSeek for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncSeek,
+        {
+            fn start_seek(self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+                let (inner, _ops, _closed_tracker) = self.project();
+                inner.start_seek(position)
+            }
+
+            fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+                let (inner, ops, _closed_tracker) = self.project();
+                match ops.next() {
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during seek, generated by partial-io",
+                        )))
+                    }
+                    _ => inner.poll_complete(cx),
+                }
+            }
+        }
+    };
+}
+
+// `AsyncSeek` where `start_seek` is itself polled (tokio 0.2, which predates the sync
+// `start_seek` that 0.3 and 1.x settled on).
+#[cfg(feature = "tokio02")]
+macro_rules! impl_partial_async_seek_split_poll {
+    ($AsyncSeek:path, $AsyncWrite:path) => {
+        impl<W> $AsyncSeek for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncSeek,
+        {
+            fn start_seek(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                position: io::SeekFrom,
+            ) -> Poll<io::Result<()>> {
+                let (inner, _ops, _closed_tracker) = self.project();
+                inner.start_seek(cx, position)
+            }
+
+            fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+                let (inner, ops, _closed_tracker) = self.project();
+                match ops.next() {
+                    Some(PartialOp::Err(err)) => {
+                        if err == io::ErrorKind::WouldBlock {
+                            // Make sure this task is rechecked.
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Err(io::Error::new(
+                            err,
+                            "error during seek, generated by partial-io",
+                        )))
+                    }
+                    _ => inner.poll_complete(cx),
+                }
+            }
+        }
+    };
+}
+
+// `AsyncBufRead` has the same shape (a `poll_fill_buf`/`consume` pair) across every ecosystem,
+// so one macro covers all four features. A `Limited(n)` op caps how much of the inner buffer
+// `poll_fill_buf` exposes until the caller `consume`s it, forcing another fill cycle to see the
+// rest -- the same way `Limited` caps a single `poll_write`/`poll_read` call.
+macro_rules! impl_partial_async_buf_read {
+    ($AsyncBufRead:path, $AsyncWrite:path) => {
+        impl<W> $AsyncBufRead for PartialAsyncWrite<W>
+        where
+            W: $AsyncWrite + $AsyncBufRead,
+        {
+            fn poll_fill_buf(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<io::Result<&[u8]>> {
+                let (inner, ops, fill_limit) = self.project_bufread();
+                if fill_limit.is_none() {
+                    match ops.next() {
+                        Some(PartialOp::Err(err)) => {
+                            if err == io::ErrorKind::WouldBlock {
+                                // Make sure this task is rechecked.
+                                cx.waker().wake_by_ref();
+                                return Poll::Pending;
+                            }
+                            return Poll::Ready(Err(io::Error::new(
+                                err,
+                                "error during fill_buf, generated by partial-io",
+                            )));
+                        }
+                        Some(PartialOp::Limited(n)) => *fill_limit = Some(n),
+                        Some(PartialOp::Unlimited) | None => {}
+                    }
+                }
+                let limit = *fill_limit;
+                match inner.poll_fill_buf(cx) {
+                    Poll::Ready(Ok(buf)) => {
+                        let len = limit.map_or(buf.len(), |n| cmp::min(n, buf.len()));
+                        Poll::Ready(Ok(&buf[..len]))
+                    }
+                    other => other,
+                }
+            }
+
+            fn consume(self: Pin<&mut Self>, amt: usize) {
+                let (inner, _ops, fill_limit) = self.project_bufread();
+                *fill_limit = None;
+                inner.consume(amt)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "tokio1")]
+impl_partial_async_write!(tokio::io::AsyncWrite, poll_shutdown);
+#[cfg(feature = "tokio1")]
+impl_partial_async_read_readbuf!(
+    tokio::io::AsyncRead,
+    tokio::io::AsyncWrite,
+    tokio::io::ReadBuf<'_>
+);
+#[cfg(feature = "tokio1")]
+impl_partial_async_seek_split!(tokio::io::AsyncSeek, tokio::io::AsyncWrite);
+#[cfg(feature = "tokio1")]
+impl_partial_async_buf_read!(tokio::io::AsyncBufRead, tokio::io::AsyncWrite);
+
+#[cfg(feature = "tokio03")]
+impl_partial_async_write!(tokio03::io::AsyncWrite, poll_shutdown);
+#[cfg(feature = "tokio03")]
+impl_partial_async_read_readbuf!(
+    tokio03::io::AsyncRead,
+    tokio03::io::AsyncWrite,
+    tokio03::io::ReadBuf<'_>
+);
+#[cfg(feature = "tokio03")]
+impl_partial_async_seek_split!(tokio03::io::AsyncSeek, tokio03::io::AsyncWrite);
+#[cfg(feature = "tokio03")]
+impl_partial_async_buf_read!(tokio03::io::AsyncBufRead, tokio03::io::AsyncWrite);
+
+#[cfg(feature = "tokio02")]
+impl_partial_async_write!(tokio02::io::AsyncWrite, poll_shutdown);
+#[cfg(feature = "tokio02")]
+impl_partial_async_read_buf!(tokio02::io::AsyncRead, tokio02::io::AsyncWrite);
+#[cfg(feature = "tokio02")]
+impl_partial_async_seek_split_poll!(tokio02::io::AsyncSeek, tokio02::io::AsyncWrite);
+#[cfg(feature = "tokio02")]
+impl_partial_async_buf_read!(tokio02::io::AsyncBufRead, tokio02::io::AsyncWrite);
+
+#[cfg(feature = "futures03")]
+impl_partial_async_write!(futures::io::AsyncWrite, poll_close);
+#[cfg(feature = "futures03")]
+impl_partial_async_read_buf!(futures::io::AsyncRead, futures::io::AsyncWrite);
+#[cfg(feature = "futures03")]
+impl_partial_async_seek_poll!(futures::io::AsyncSeek, futures::io::AsyncWrite);
+#[cfg(feature = "futures03")]
+impl_partial_async_buf_read!(futures::io::AsyncBufRead, futures::io::AsyncWrite);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,4 +805,273 @@ mod tests {
     fn test_sendable() {
         assert_send::<PartialAsyncWrite<File>>();
     }
+
+    // A waker that does nothing, so polls in these tests can assert on `Poll::Pending` without
+    // driving an executor. Only the `tokio1` feature trait impls are exercised below, so this
+    // (and its `std::task` imports) would otherwise be dead code under any other single feature.
+    #[cfg(feature = "tokio1")]
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    #[cfg(feature = "tokio1")]
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        fn raw_waker() -> RawWaker {
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        unsafe { Waker::from_raw(raw_waker()) }
+    }
+
+    // These exercise the trait impls, so they need a concrete `AsyncWrite`/`AsyncRead`/
+    // `AsyncBufRead` in scope; `tokio1` is used since it's the feature the module doctest above
+    // already depends on.
+    #[cfg(feature = "tokio1")]
+    mod tokio1 {
+        use super::*;
+
+        use std::io::Cursor;
+
+        use tokio::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+        #[test]
+        fn tracked_write_after_shutdown_errors() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new_tracked(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Unlimited; 8],
+            );
+            assert!(matches!(
+                Pin::new(&mut writer).poll_shutdown(&mut cx),
+                Poll::Ready(Ok(()))
+            ));
+            assert!(writer.is_closed());
+            let err = match Pin::new(&mut writer).poll_write(&mut cx, b"x") {
+                Poll::Ready(Err(err)) => err,
+                other => panic!("expected an error, got {:?}", other),
+            };
+            assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+        }
+
+        #[test]
+        fn tracked_double_shutdown_errors() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new_tracked(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Unlimited; 8],
+            );
+            assert!(matches!(
+                Pin::new(&mut writer).poll_shutdown(&mut cx),
+                Poll::Ready(Ok(()))
+            ));
+            let err = match Pin::new(&mut writer).poll_shutdown(&mut cx) {
+                Poll::Ready(Err(err)) => err,
+                other => panic!("expected an error, got {:?}", other),
+            };
+            assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+        }
+
+        #[test]
+        #[should_panic(expected = "shutdown called on a PartialAsyncWrite after shutdown")]
+        fn tracked_double_shutdown_panics_when_configured() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new_tracked(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Unlimited; 8],
+            );
+            writer.set_closed_behavior(ClosedBehavior::Panic);
+            assert!(matches!(
+                Pin::new(&mut writer).poll_shutdown(&mut cx),
+                Poll::Ready(Ok(()))
+            ));
+            let _ = Pin::new(&mut writer).poll_shutdown(&mut cx);
+        }
+
+        #[test]
+        fn tracked_is_flushed_reflects_completed_writes_only() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new_tracked(
+                Cursor::new(Vec::new()),
+                vec![
+                    PartialOp::Err(io::ErrorKind::WouldBlock),
+                    PartialOp::Unlimited,
+                ],
+            );
+            assert!(writer.is_flushed());
+
+            // A `WouldBlock` write writes nothing, so it must not un-flush the writer.
+            assert!(matches!(
+                Pin::new(&mut writer).poll_write(&mut cx, b"x"),
+                Poll::Pending
+            ));
+            assert!(writer.is_flushed());
+
+            // A write that actually lands does un-flush it, until the next `poll_flush`.
+            match Pin::new(&mut writer).poll_write(&mut cx, b"x") {
+                Poll::Ready(Ok(n)) => assert_eq!(n, 1),
+                other => panic!("expected a completed write, got {:?}", other),
+            }
+            assert!(!writer.is_flushed());
+            assert!(matches!(
+                Pin::new(&mut writer).poll_flush(&mut cx),
+                Poll::Ready(Ok(()))
+            ));
+            assert!(writer.is_flushed());
+        }
+
+        #[test]
+        fn assert_unmoved_allows_polling_repeatedly_from_the_same_place() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = Box::pin(PartialAsyncWrite::assert_unmoved(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Unlimited; 4],
+            ));
+            match writer.as_mut().poll_write(&mut cx, b"a") {
+                Poll::Ready(Ok(n)) => assert_eq!(n, 1),
+                other => panic!("expected a completed write, got {:?}", other),
+            }
+            match writer.as_mut().poll_write(&mut cx, b"b") {
+                Poll::Ready(Ok(n)) => assert_eq!(n, 1),
+                other => panic!("expected a completed write, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn assert_unmoved_panics_when_moved_between_polls() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut first = Box::pin(PartialAsyncWrite::assert_unmoved(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Unlimited; 4],
+            ));
+            let _ = first.as_mut().poll_write(&mut cx, b"a");
+
+            // Simulate a buggy future that moves a pinned value between polls: relocate its
+            // bytes to a new heap allocation and poll from there instead. `first`'s storage is
+            // forgotten rather than dropped, since it no longer holds a valid value. The panic
+            // is caught explicitly (rather than via `#[should_panic]`) so `moved` can be
+            // forgotten too -- its `Drop` impl would otherwise run the same now-poisoned check
+            // again while already unwinding, aborting the process instead of failing the test.
+            let moved = unsafe { ptr::read(&*first) };
+            mem::forget(first);
+            let mut moved = Box::pin(moved);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                moved.as_mut().poll_write(&mut cx, b"b")
+            }));
+            mem::forget(moved);
+
+            let err = result.expect_err("expected poll_write to panic after the value was moved");
+            let message = err
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| err.downcast_ref::<&str>().copied())
+                .expect("panic payload should be a string");
+            assert!(message.contains("AssertUnmoved value was moved between polls"));
+        }
+
+        #[test]
+        fn poll_read_is_capped_by_limited_ops() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new(
+                Cursor::new(b"abcdef".to_vec()),
+                vec![PartialOp::Limited(3)],
+            );
+            let mut buf = [0u8; 6];
+            let mut read_buf = tokio::io::ReadBuf::new(&mut buf);
+            assert!(matches!(
+                Pin::new(&mut writer).poll_read(&mut cx, &mut read_buf),
+                Poll::Ready(Ok(()))
+            ));
+            assert_eq!(read_buf.filled(), b"abc");
+        }
+
+        #[test]
+        fn poll_fill_buf_is_capped_until_consume() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new(
+                Cursor::new(b"abcdef".to_vec()),
+                vec![PartialOp::Limited(2)],
+            );
+            let capped = match Pin::new(&mut writer).poll_fill_buf(&mut cx) {
+                Poll::Ready(Ok(buf)) => buf.to_vec(),
+                other => panic!("expected a filled buffer, got {:?}", other),
+            };
+            assert_eq!(capped, b"ab");
+
+            // Before `consume`, repeated fills see the same capped slice.
+            let capped_again = match Pin::new(&mut writer).poll_fill_buf(&mut cx) {
+                Poll::Ready(Ok(buf)) => buf.to_vec(),
+                other => panic!("expected a filled buffer, got {:?}", other),
+            };
+            assert_eq!(capped_again, b"ab");
+
+            Pin::new(&mut writer).consume(2);
+
+            // After `consume`, the cap is gone and the rest of the buffer is visible.
+            let rest = match Pin::new(&mut writer).poll_fill_buf(&mut cx) {
+                Poll::Ready(Ok(buf)) => buf.to_vec(),
+                other => panic!("expected a filled buffer, got {:?}", other),
+            };
+            assert_eq!(rest, b"cdef");
+        }
+
+        #[test]
+        fn seek_would_block_then_completes() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::new(
+                Cursor::new(b"abcdef".to_vec()),
+                vec![
+                    PartialOp::Err(io::ErrorKind::WouldBlock),
+                    PartialOp::Unlimited,
+                ],
+            );
+            Pin::new(&mut writer)
+                .start_seek(io::SeekFrom::Start(2))
+                .unwrap();
+            assert!(matches!(
+                Pin::new(&mut writer).poll_complete(&mut cx),
+                Poll::Pending
+            ));
+            match Pin::new(&mut writer).poll_complete(&mut cx) {
+                Poll::Ready(Ok(pos)) => assert_eq!(pos, 2),
+                other => panic!("expected a completed seek, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn interleave_pending_yields_one_pending_before_every_op() {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut writer = PartialAsyncWrite::interleave_pending(
+                Cursor::new(Vec::new()),
+                vec![PartialOp::Limited(2), PartialOp::Unlimited],
+            );
+            assert!(matches!(
+                Pin::new(&mut writer).poll_write(&mut cx, b"abcd"),
+                Poll::Pending
+            ));
+            match Pin::new(&mut writer).poll_write(&mut cx, b"abcd") {
+                Poll::Ready(Ok(n)) => assert_eq!(n, 2),
+                other => panic!("expected a completed write, got {:?}", other),
+            }
+            assert!(matches!(
+                Pin::new(&mut writer).poll_write(&mut cx, b"cd"),
+                Poll::Pending
+            ));
+            match Pin::new(&mut writer).poll_write(&mut cx, b"cd") {
+                Poll::Ready(Ok(n)) => assert_eq!(n, 2),
+                other => panic!("expected a completed write, got {:?}", other),
+            }
+        }
+    }
 }